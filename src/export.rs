@@ -0,0 +1,327 @@
+// Copyright (c) 2025 Jan Holthuis <jan.holthuis@rub.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a copy
+// of the MPL was not distributed with this file, You can obtain one at
+// http://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Columnar export of decoded PDB rows to Apache Arrow / Parquet, enabled via the `arrow`
+//! feature.
+//!
+//! [`write_parquet`] drains a [`PdbReader`] via [`PdbReader::try_rows`] as it goes, so the whole
+//! PDB never needs to be resident in memory at once, and a corrupt table surfaces as an
+//! [`Error`](crate::Error) instead of silently truncating the export. Each table is written to
+//! its own Parquet file with a schema typed to its [`Row`] variant, flushing a row group every
+//! [`BATCH_SIZE`] rows. A row whose variant isn't one of the tables [`TableBatch::for_row`]
+//! recognizes yet is counted and logged, rather than silently dropped.
+
+use std::{collections::HashMap, fs::File, path::Path, sync::Arc};
+
+use arrow::array::{ArrayRef, RecordBatch, StringBuilder, UInt32Builder};
+use arrow::datatypes::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use crate::pdb::{PageType, Row};
+use crate::{PdbReader, Result};
+
+/// Number of rows accumulated per table before a [`RecordBatch`] is flushed to a new row group.
+const BATCH_SIZE: usize = 8192;
+
+/// Drains `reader`, writing one Parquet file per PDB table type into `dir`.
+///
+/// Rows are grouped by table as they arrive from `reader` and flushed in batches of
+/// [`BATCH_SIZE`], so the whole PDB never needs to be resident in memory at once. A read error
+/// partway through the file (per [`PdbReader::try_rows`]) aborts the export with that error
+/// rather than silently writing a truncated file.
+pub fn write_parquet(mut reader: PdbReader, dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+
+    let mut tables: HashMap<PageType, TableBatch> = HashMap::new();
+    let mut writers: HashMap<PageType, ArrowWriter<File>> = HashMap::new();
+    let mut unsupported_rows = 0usize;
+
+    for row in reader.try_rows() {
+        let row = row?;
+        let Some((page_type, fresh_batch)) = TableBatch::for_row(&row) else {
+            unsupported_rows += 1;
+            continue;
+        };
+
+        let batch = tables.entry(page_type).or_insert(fresh_batch);
+        batch.push(&row);
+
+        if batch.len() >= BATCH_SIZE {
+            flush(dir, page_type, batch, &mut writers)?;
+        }
+    }
+
+    if unsupported_rows > 0 {
+        log::warn!(
+            "export skipped {unsupported_rows} row(s) whose table has no typed Arrow schema yet"
+        );
+    }
+
+    for (page_type, mut batch) in tables {
+        if !batch.is_empty() {
+            flush(dir, page_type, &mut batch, &mut writers)?;
+        }
+    }
+
+    for (_, writer) in writers {
+        writer.close()?;
+    }
+
+    Ok(())
+}
+
+fn flush(
+    dir: &Path,
+    page_type: PageType,
+    batch: &mut TableBatch,
+    writers: &mut HashMap<PageType, ArrowWriter<File>>,
+) -> Result<()> {
+    let record_batch = batch.take()?;
+
+    if !writers.contains_key(&page_type) {
+        let path = dir.join(format!("{page_type:?}.parquet"));
+        let file = File::create(path)?;
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let writer = ArrowWriter::try_new(file, record_batch.schema(), Some(props))?;
+        writers.insert(page_type, writer);
+    }
+
+    let writer = writers
+        .get_mut(&page_type)
+        .expect("writer was just inserted");
+    writer.write(&record_batch)?;
+    // Close the row group, so every flushed batch becomes its own Parquet row group.
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Accumulates rows for a single table, in that table's typed Arrow schema, until enough have
+/// arrived to flush a [`RecordBatch`].
+enum TableBatch {
+    Track {
+        id: UInt32Builder,
+        title: StringBuilder,
+        artist_id: UInt32Builder,
+        album_id: UInt32Builder,
+        bpm: UInt32Builder,
+        duration: UInt32Builder,
+    },
+    Artist(NamedRowBatch),
+    Album(NamedRowBatch),
+    Playlist(NamedRowBatch),
+    Key(NamedRowBatch),
+    Color(NamedRowBatch),
+    Genre(NamedRowBatch),
+    PlaylistEntry {
+        playlist_id: UInt32Builder,
+        track_id: UInt32Builder,
+        entry_index: UInt32Builder,
+    },
+}
+
+/// Builder shared by the tables that are just an `id` plus a `name`: artists, albums, playlists,
+/// keys, colors, genres.
+struct NamedRowBatch {
+    id: UInt32Builder,
+    name: StringBuilder,
+}
+
+impl NamedRowBatch {
+    fn new() -> Self {
+        Self {
+            id: UInt32Builder::new(),
+            name: StringBuilder::new(),
+        }
+    }
+}
+
+impl TableBatch {
+    /// Returns the table `row` belongs to, paired with a freshly-initialized batch for it, or
+    /// `None` if export doesn't have a typed schema for this row variant yet. This is the only
+    /// place a `Row` variant is mapped to a table: pairing the two here, instead of keying a
+    /// separate `PageType -> TableBatch` constructor by table type, means a variant this module
+    /// doesn't yet support is simply skipped instead of falling through to some other table's
+    /// builder and panicking in [`TableBatch::push`].
+    fn for_row(row: &Row) -> Option<(PageType, Self)> {
+        let batch = match row {
+            Row::Track(_) => (
+                PageType::Tracks,
+                Self::Track {
+                    id: UInt32Builder::new(),
+                    title: StringBuilder::new(),
+                    artist_id: UInt32Builder::new(),
+                    album_id: UInt32Builder::new(),
+                    bpm: UInt32Builder::new(),
+                    duration: UInt32Builder::new(),
+                },
+            ),
+            Row::Artist(_) => (PageType::Artists, Self::Artist(NamedRowBatch::new())),
+            Row::Album(_) => (PageType::Albums, Self::Album(NamedRowBatch::new())),
+            Row::Playlist(_) => (PageType::Playlists, Self::Playlist(NamedRowBatch::new())),
+            Row::Key(_) => (PageType::Keys, Self::Key(NamedRowBatch::new())),
+            Row::Color(_) => (PageType::Colors, Self::Color(NamedRowBatch::new())),
+            Row::Genre(_) => (PageType::Genres, Self::Genre(NamedRowBatch::new())),
+            Row::PlaylistEntry(_) => (
+                PageType::PlaylistEntries,
+                Self::PlaylistEntry {
+                    playlist_id: UInt32Builder::new(),
+                    track_id: UInt32Builder::new(),
+                    entry_index: UInt32Builder::new(),
+                },
+            ),
+            _ => return None,
+        };
+        Some(batch)
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Track { id, .. } => id.len(),
+            Self::Artist(batch)
+            | Self::Album(batch)
+            | Self::Playlist(batch)
+            | Self::Key(batch)
+            | Self::Color(batch)
+            | Self::Genre(batch) => batch.id.len(),
+            Self::PlaylistEntry { playlist_id, .. } => playlist_id.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&mut self, row: &Row) {
+        match (self, row) {
+            (
+                Self::Track {
+                    id,
+                    title,
+                    artist_id,
+                    album_id,
+                    bpm,
+                    duration,
+                },
+                Row::Track(track),
+            ) => {
+                id.append_value(track.id);
+                title.append_value(track.title.to_string());
+                artist_id.append_value(track.artist_id);
+                album_id.append_value(track.album_id);
+                bpm.append_value(track.bpm);
+                duration.append_value(track.duration);
+            }
+            (Self::Artist(batch), Row::Artist(artist)) => {
+                batch.id.append_value(artist.id);
+                batch.name.append_value(artist.name.to_string());
+            }
+            (Self::Album(batch), Row::Album(album)) => {
+                batch.id.append_value(album.id);
+                batch.name.append_value(album.name.to_string());
+            }
+            (Self::Playlist(batch), Row::Playlist(playlist)) => {
+                batch.id.append_value(playlist.id);
+                batch.name.append_value(playlist.name.to_string());
+            }
+            (Self::Key(batch), Row::Key(key)) => {
+                batch.id.append_value(key.id);
+                batch.name.append_value(key.name.to_string());
+            }
+            (Self::Color(batch), Row::Color(color)) => {
+                batch.id.append_value(color.id);
+                batch.name.append_value(color.name.to_string());
+            }
+            (Self::Genre(batch), Row::Genre(genre)) => {
+                batch.id.append_value(genre.id);
+                batch.name.append_value(genre.name.to_string());
+            }
+            (
+                Self::PlaylistEntry {
+                    playlist_id,
+                    track_id,
+                    entry_index,
+                },
+                Row::PlaylistEntry(entry),
+            ) => {
+                playlist_id.append_value(entry.playlist_id);
+                track_id.append_value(entry.track_id);
+                entry_index.append_value(entry.entry_index);
+            }
+            _ => unreachable!("TableBatch is only ever pushed rows matching its own table"),
+        }
+    }
+
+    /// Drains the accumulated rows into a [`RecordBatch`], resetting this builder.
+    fn take(&mut self) -> Result<RecordBatch> {
+        let (schema, columns) = match self {
+            Self::Track {
+                id,
+                title,
+                artist_id,
+                album_id,
+                bpm,
+                duration,
+            } => (
+                Schema::new(vec![
+                    Field::new("id", DataType::UInt32, false),
+                    Field::new("title", DataType::Utf8, false),
+                    Field::new("artist_id", DataType::UInt32, false),
+                    Field::new("album_id", DataType::UInt32, false),
+                    Field::new("bpm", DataType::UInt32, false),
+                    Field::new("duration", DataType::UInt32, false),
+                ]),
+                vec![
+                    Arc::new(id.finish()) as ArrayRef,
+                    Arc::new(title.finish()),
+                    Arc::new(artist_id.finish()),
+                    Arc::new(album_id.finish()),
+                    Arc::new(bpm.finish()),
+                    Arc::new(duration.finish()),
+                ],
+            ),
+            Self::Artist(batch)
+            | Self::Album(batch)
+            | Self::Playlist(batch)
+            | Self::Key(batch)
+            | Self::Color(batch)
+            | Self::Genre(batch) => (
+                Schema::new(vec![
+                    Field::new("id", DataType::UInt32, false),
+                    Field::new("name", DataType::Utf8, false),
+                ]),
+                vec![
+                    Arc::new(batch.id.finish()) as ArrayRef,
+                    Arc::new(batch.name.finish()),
+                ],
+            ),
+            Self::PlaylistEntry {
+                playlist_id,
+                track_id,
+                entry_index,
+            } => (
+                Schema::new(vec![
+                    Field::new("playlist_id", DataType::UInt32, false),
+                    Field::new("track_id", DataType::UInt32, false),
+                    Field::new("entry_index", DataType::UInt32, false),
+                ]),
+                vec![
+                    Arc::new(playlist_id.finish()) as ArrayRef,
+                    Arc::new(track_id.finish()),
+                    Arc::new(entry_index.finish()),
+                ],
+            ),
+        };
+
+        Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+    }
+}
@@ -0,0 +1,268 @@
+// Copyright (c) 2025 Jan Holthuis <jan.holthuis@rub.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a copy
+// of the MPL was not distributed with this file, You can obtain one at
+// http://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! A random-access index over decoded PDB rows, resolving cross-references by primary key
+//! instead of repeatedly scanning the linear row list.
+//!
+//! Building a [`PdbIndex`] walks the rows once and groups them by table, so that resolving a
+//! playlist entry's track, or a track's artist/album/key, becomes an `O(1)` lookup rather than a
+//! full scan of [`PdbRows`]. [`PdbIndex::build`] takes ownership of the rows it indexes and moves
+//! them into its maps instead of cloning, so indexing a file doesn't cost a second copy of every
+//! row on top of the [`PdbRows`] that was already materialized to build it.
+
+use std::collections::HashMap;
+
+use crate::pdb::{Album, Artist, Color, Genre, Key, Playlist, PlaylistEntry, Row, Track};
+use crate::PdbRows;
+
+/// `O(1)` lookup by primary key over a collection of [`PdbRows`], built once up front.
+#[derive(Debug, Default)]
+pub struct PdbIndex {
+    tracks: HashMap<u32, Track>,
+    artists: HashMap<u32, Artist>,
+    albums: HashMap<u32, Album>,
+    playlists: HashMap<u32, Playlist>,
+    keys: HashMap<u32, Key>,
+    colors: HashMap<u32, Color>,
+    genres: HashMap<u32, Genre>,
+    playlist_entries: Vec<PlaylistEntry>,
+}
+
+impl PdbIndex {
+    /// Builds an index over `rows`, grouping them by table as it goes.
+    ///
+    /// Takes ownership of `rows` and moves each one into its table's map, rather than borrowing
+    /// and cloning, so the rows aren't held twice in memory while the index is built.
+    #[must_use]
+    pub fn build(rows: PdbRows) -> Self {
+        let mut index = Self::default();
+
+        for row in rows.into_rows() {
+            match row {
+                Row::Track(track) => {
+                    index.tracks.insert(track.id, track);
+                }
+                Row::Artist(artist) => {
+                    index.artists.insert(artist.id, artist);
+                }
+                Row::Album(album) => {
+                    index.albums.insert(album.id, album);
+                }
+                Row::Playlist(playlist) => {
+                    index.playlists.insert(playlist.id, playlist);
+                }
+                Row::Key(key) => {
+                    index.keys.insert(key.id, key);
+                }
+                Row::Color(color) => {
+                    index.colors.insert(color.id, color);
+                }
+                Row::Genre(genre) => {
+                    index.genres.insert(genre.id, genre);
+                }
+                Row::PlaylistEntry(entry) => {
+                    index.playlist_entries.push(entry);
+                }
+                _ => {}
+            }
+        }
+
+        index
+    }
+
+    /// Looks up a track by its row ID.
+    #[must_use]
+    pub fn get_track(&self, id: u32) -> Option<&Track> {
+        self.tracks.get(&id)
+    }
+
+    /// Looks up an artist by its row ID.
+    #[must_use]
+    pub fn get_artist(&self, id: u32) -> Option<&Artist> {
+        self.artists.get(&id)
+    }
+
+    /// Looks up an album by its row ID.
+    #[must_use]
+    pub fn get_album(&self, id: u32) -> Option<&Album> {
+        self.albums.get(&id)
+    }
+
+    /// Looks up a playlist by its row ID.
+    #[must_use]
+    pub fn get_playlist(&self, id: u32) -> Option<&Playlist> {
+        self.playlists.get(&id)
+    }
+
+    /// Looks up a musical key by its row ID.
+    #[must_use]
+    pub fn get_key(&self, id: u32) -> Option<&Key> {
+        self.keys.get(&id)
+    }
+
+    /// Looks up a color tag by its row ID.
+    #[must_use]
+    pub fn get_color(&self, id: u32) -> Option<&Color> {
+        self.colors.get(&id)
+    }
+
+    /// Looks up a genre by its row ID.
+    #[must_use]
+    pub fn get_genre(&self, id: u32) -> Option<&Genre> {
+        self.genres.get(&id)
+    }
+
+    /// Resolves a [`Reference`] to the row it points at, dispatching to the table-specific
+    /// getter. Useful when walking a chain of cross-references (e.g. a track's artist, then that
+    /// artist's own rows) without matching on the table type by hand at every step.
+    #[must_use]
+    pub fn resolve(&self, reference: Reference) -> Option<Resolved<'_>> {
+        match reference {
+            Reference::Track(id) => self.get_track(id).map(Resolved::Track),
+            Reference::Artist(id) => self.get_artist(id).map(Resolved::Artist),
+            Reference::Album(id) => self.get_album(id).map(Resolved::Album),
+            Reference::Playlist(id) => self.get_playlist(id).map(Resolved::Playlist),
+            Reference::Key(id) => self.get_key(id).map(Resolved::Key),
+            Reference::Color(id) => self.get_color(id).map(Resolved::Color),
+            Reference::Genre(id) => self.get_genre(id).map(Resolved::Genre),
+        }
+    }
+
+    /// Joins `playlist_id` to its track rows, in playlist order, skipping any entry whose track
+    /// wasn't found (e.g. because of a [`crate::PdbReader`] table projection).
+    #[must_use]
+    pub fn playlist_tracks(&self, playlist_id: u32) -> Vec<&Track> {
+        let mut entries: Vec<&PlaylistEntry> = self
+            .playlist_entries
+            .iter()
+            .filter(|entry| entry.playlist_id == playlist_id)
+            .collect();
+        entries.sort_by_key(|entry| entry.entry_index);
+
+        entries
+            .into_iter()
+            .filter_map(|entry| self.get_track(entry.track_id))
+            .collect()
+    }
+}
+
+/// A row ID paired with the table it belongs to, for use with [`PdbIndex::resolve`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reference {
+    /// A track row ID, e.g. a playlist entry's `track_id`.
+    Track(u32),
+    /// An artist row ID, e.g. a track's `artist_id`.
+    Artist(u32),
+    /// An album row ID, e.g. a track's `album_id`.
+    Album(u32),
+    /// A playlist row ID.
+    Playlist(u32),
+    /// A musical key row ID, e.g. a track's `key_id`.
+    Key(u32),
+    /// A color tag row ID.
+    Color(u32),
+    /// A genre row ID, e.g. a track's `genre_id`.
+    Genre(u32),
+}
+
+/// The row a [`Reference`] resolved to, per [`PdbIndex::resolve`].
+#[derive(Debug, Clone, Copy)]
+pub enum Resolved<'a> {
+    /// See [`Reference::Track`].
+    Track(&'a Track),
+    /// See [`Reference::Artist`].
+    Artist(&'a Artist),
+    /// See [`Reference::Album`].
+    Album(&'a Album),
+    /// See [`Reference::Playlist`].
+    Playlist(&'a Playlist),
+    /// See [`Reference::Key`].
+    Key(&'a Key),
+    /// See [`Reference::Color`].
+    Color(&'a Color),
+    /// See [`Reference::Genre`].
+    Genre(&'a Genre),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: u32) -> Track {
+        Track {
+            id,
+            title: format!("track {id}"),
+            artist_id: 0,
+            album_id: 0,
+            bpm: 0,
+            duration: 0,
+        }
+    }
+
+    fn entry(playlist_id: u32, track_id: u32, entry_index: u32) -> PlaylistEntry {
+        PlaylistEntry {
+            playlist_id,
+            track_id,
+            entry_index,
+        }
+    }
+
+    fn index_with(rows: Vec<Row>) -> PdbIndex {
+        PdbIndex::build(PdbRows { rows })
+    }
+
+    #[test]
+    fn playlist_tracks_orders_by_entry_index_not_insertion_order() {
+        let index = index_with(vec![
+            Row::Track(track(1)),
+            Row::Track(track(2)),
+            Row::PlaylistEntry(entry(10, 2, 1)),
+            Row::PlaylistEntry(entry(10, 1, 0)),
+        ]);
+
+        let ids: Vec<u32> = index.playlist_tracks(10).iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn playlist_tracks_skips_entries_whose_track_is_missing() {
+        let index = index_with(vec![
+            Row::Track(track(1)),
+            Row::PlaylistEntry(entry(10, 1, 0)),
+            Row::PlaylistEntry(entry(10, 99, 1)),
+        ]);
+
+        let ids: Vec<u32> = index.playlist_tracks(10).iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1]);
+    }
+
+    #[test]
+    fn playlist_tracks_ignores_entries_from_other_playlists() {
+        let index = index_with(vec![
+            Row::Track(track(1)),
+            Row::Track(track(2)),
+            Row::PlaylistEntry(entry(10, 1, 0)),
+            Row::PlaylistEntry(entry(20, 2, 0)),
+        ]);
+
+        let ids: Vec<u32> = index.playlist_tracks(10).iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1]);
+    }
+
+    #[test]
+    fn resolve_dispatches_to_the_matching_table() {
+        let index = index_with(vec![Row::Track(track(1))]);
+
+        match index.resolve(Reference::Track(1)) {
+            Some(Resolved::Track(t)) => assert_eq!(t.id, 1),
+            other => panic!("expected Some(Resolved::Track), got {other:?}"),
+        }
+        assert!(index.resolve(Reference::Track(2)).is_none());
+        assert!(index.resolve(Reference::Artist(1)).is_none());
+    }
+}
@@ -24,48 +24,293 @@
 #![cfg_attr(not(debug_assertions), deny(clippy::used_underscore_binding))]
 
 pub mod anlz;
+#[cfg(feature = "arrow")]
+pub mod export;
+pub mod index;
 pub mod pdb;
 pub mod setting;
 pub mod util;
+pub mod writer;
 pub mod xml;
 pub(crate) mod xor;
 
 use binrw::BinRead;
 use std::{path::PathBuf, slice};
 
-use crate::pdb::{DatabaseType, Header, PageContent, Row};
+use crate::pdb::{DatabaseType, Header, PageContent, PageType, Row};
 pub use crate::util::RekordcrateError as Error;
 pub use crate::util::RekordcrateResult as Result;
 
 /// Reads all data rows from a PDB file and returns an owned collection for borrowed iteration.
+///
+/// This is a convenience wrapper around [`PdbReader`] for callers who are fine with the whole
+/// file being resident in memory, and who want a read error anywhere in the file to abort the
+/// whole read. For large libraries, early termination, or tolerance of a corrupt page, use
+/// [`PdbReader::try_rows`] directly.
 pub fn iter_pdb_rows(path: &PathBuf, typ: DatabaseType) -> Result<PdbRows> {
-    let mut reader = std::fs::File::open(path)?;
-    let header = Header::read_args(&mut reader, (typ,))?;
-
-    let tables_len = header.tables.len();
-    println!("PDB header - # of tables: {}, page size: {}", tables_len, header.page_size);
-
-    // estimate capacity to reduce resize costs 
-    let mut rows = Vec::with_capacity(tables_len * 128); 
-    for table in &header.tables {
-        for page in header.read_pages(
-            &mut reader,
-            binrw::Endian::NATIVE,
-            (&table.first_page, &table.last_page, typ),
-        )? {
-            if let PageContent::Data(data_content) = page.content {
-                for row_group in data_content.row_groups {
-                    rows.extend(row_group.into_rows());
+    let mut reader = PdbReader::open(path, typ)?;
+
+    let tables_len = reader.header().tables.len();
+    // estimate capacity to reduce resize costs
+    let mut rows = Vec::with_capacity(tables_len * 128);
+    for row in reader.try_rows() {
+        rows.push(row?);
+    }
+
+    Ok(PdbRows { rows })
+}
+
+/// Like [`iter_pdb_rows`], but only decodes the tables listed in `projection`, never reading the
+/// page ranges of any other table. An empty `projection` yields zero rows.
+pub fn iter_pdb_rows_filtered(
+    path: &PathBuf,
+    typ: DatabaseType,
+    projection: &[PageType],
+) -> Result<PdbRows> {
+    let mut reader = PdbReader::open(path, typ)?.with_tables(projection.iter().copied());
+
+    let mut rows = Vec::new();
+    for row in reader.try_rows() {
+        rows.push(row?);
+    }
+
+    Ok(PdbRows { rows })
+}
+
+/// Streaming reader over a PDB file that decodes rows lazily, table by table and page by page.
+///
+/// Unlike [`iter_pdb_rows`], which fully buffers every row before returning, `PdbReader` only
+/// reads and decodes one [`PageContent::Data`] page at a time as the iterator is advanced, so
+/// memory use is bounded by a single page rather than by the largest table (or the whole file),
+/// and a caller can stop early without paying for the rest of the file. This relies on a table's
+/// `first_page`/`last_page` being sequential page indices spanning the table's pages, so a single
+/// page can be requested from [`Header::read_pages`] by passing the same index as both bounds.
+#[derive(Debug)]
+pub struct PdbReader {
+    reader: std::fs::File,
+    header: Header,
+    typ: DatabaseType,
+    table_idx: usize,
+    /// Index of the next page to read within the current table, once one has been started.
+    page_cursor: Option<u32>,
+    pages: std::vec::IntoIter<crate::pdb::Page>,
+    rows: std::vec::IntoIter<Row>,
+    done: bool,
+    error_mode: ErrorMode,
+    stats: PdbStats,
+    projection: Option<std::collections::HashSet<PageType>>,
+}
+
+/// Controls how [`PdbReader::try_rows`] reacts to a read error partway through the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorMode {
+    /// Stop iteration and surface the error to the caller. This is the default.
+    #[default]
+    Fail,
+    /// Log the error, skip the offending page, and continue with the next page — or the next
+    /// table, if the failing page was the last one in its table.
+    SkipPage,
+}
+
+/// Summary statistics collected while a [`PdbReader`] is iterated.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PdbStats {
+    /// Number of tables present in the PDB header.
+    pub tables: usize,
+    /// Page size, in bytes, as recorded in the PDB header.
+    pub page_size: u32,
+    /// Total number of rows successfully decoded so far.
+    pub rows_read: usize,
+    /// Number of pages skipped after a read error (only non-zero under [`ErrorMode::SkipPage`]).
+    pub pages_skipped: usize,
+}
+
+impl PdbReader {
+    /// Opens `path` and reads the PDB header. No row data is decoded until the reader is
+    /// iterated.
+    pub fn open(path: &PathBuf, typ: DatabaseType) -> Result<Self> {
+        let mut reader = std::fs::File::open(path)?;
+        let header = Header::read_args(&mut reader, (typ,))?;
+        let stats = PdbStats {
+            tables: header.tables.len(),
+            page_size: header.page_size,
+            rows_read: 0,
+            pages_skipped: 0,
+        };
+        Ok(Self {
+            reader,
+            header,
+            typ,
+            table_idx: 0,
+            page_cursor: None,
+            pages: Vec::new().into_iter(),
+            rows: Vec::new().into_iter(),
+            done: false,
+            error_mode: ErrorMode::default(),
+            stats,
+            projection: None,
+        })
+    }
+
+    /// Returns the parsed PDB header, including table count and page size.
+    #[must_use]
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Sets the policy used when a page fails to read. Defaults to [`ErrorMode::Fail`].
+    #[must_use]
+    pub fn with_error_mode(mut self, error_mode: ErrorMode) -> Self {
+        self.error_mode = error_mode;
+        self
+    }
+
+    /// Restricts iteration to the given table types, never reading the page ranges of any other
+    /// table. Passing an empty list yields zero rows, while [`PdbReader::header`] still reports
+    /// the full table count and page size.
+    #[must_use]
+    pub fn with_tables(mut self, tables: impl IntoIterator<Item = PageType>) -> Self {
+        self.projection = Some(tables.into_iter().collect());
+        self
+    }
+
+    /// Returns the statistics collected so far, including rows read and pages skipped.
+    #[must_use]
+    pub fn stats(&self) -> PdbStats {
+        self.stats
+    }
+
+    /// Iterates rows as `Result<Row>`, so a single corrupt page can be reported (or skipped,
+    /// per [`ErrorMode`]) instead of discarding the rest of the file.
+    pub fn try_rows(&mut self) -> TryRows<'_> {
+        TryRows { reader: self }
+    }
+
+    /// Reads the next single page of the current (or next eligible) table, making it available
+    /// to `next()`. Tables excluded by [`PdbReader::with_tables`] are skipped without reading
+    /// their page ranges. Returns `Ok(false)` once every table has been visited.
+    fn advance_table(&mut self) -> Result<bool> {
+        while self.table_idx < self.header.tables.len() {
+            let table = &self.header.tables[self.table_idx];
+
+            if !is_table_included(self.projection.as_ref(), table.page_type) {
+                self.table_idx += 1;
+                self.page_cursor = None;
+                continue;
+            }
+
+            let page_index = self.page_cursor.unwrap_or(table.first_page);
+
+            // Advance the cursor before reading, so a read error still leaves the reader
+            // pointing at the next page/table instead of retrying the failing one forever.
+            match next_page_cursor(page_index, table.last_page) {
+                Some(next) => self.page_cursor = Some(next),
+                None => {
+                    self.table_idx += 1;
+                    self.page_cursor = None;
                 }
             }
+
+            let pages: Vec<_> = self
+                .header
+                .read_pages(
+                    &mut self.reader,
+                    binrw::Endian::NATIVE,
+                    (&page_index, &page_index, self.typ),
+                )?
+                .into_iter()
+                .collect();
+
+            if !pages.is_empty() {
+                self.pages = pages.into_iter();
+                return Ok(true);
+            }
         }
+
+        Ok(false)
+    }
+}
+
+/// Returns whether `page_type` should be read, given an optional table projection. Pure so the
+/// projection membership check can be tested without opening a real PDB file.
+fn is_table_included(
+    projection: Option<&std::collections::HashSet<PageType>>,
+    page_type: PageType,
+) -> bool {
+    projection.map_or(true, |projection| projection.contains(&page_type))
+}
+
+/// Given the page index just queued and its table's `last_page`, returns the next page index to
+/// request, or `None` if `page_index` was the table's last page. Pure so the cursor-advancement
+/// and last-page detection can be tested without a real PDB file.
+fn next_page_cursor(page_index: u32, last_page: u32) -> Option<u32> {
+    if page_index == last_page {
+        None
+    } else {
+        Some(page_index + 1)
     }
+}
 
-    println!("PDB read complete.");
-    let row_avg = rows.len() as f32 / tables_len as f32;
-    println!("total rows read: {}, rows per table average: {}", rows.len(), row_avg);
+impl Iterator for PdbReader {
+    type Item = Row;
 
-    Ok(PdbRows { rows })
+    fn next(&mut self) -> Option<Row> {
+        self.try_rows().next().and_then(std::result::Result::ok)
+    }
+}
+
+/// Fallible row iterator returned by [`PdbReader::try_rows`].
+#[derive(Debug)]
+pub struct TryRows<'a> {
+    reader: &'a mut PdbReader,
+}
+
+impl<'a> Iterator for TryRows<'a> {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reader.done {
+            return None;
+        }
+
+        loop {
+            if let Some(row) = self.reader.rows.next() {
+                self.reader.stats.rows_read += 1;
+                return Some(Ok(row));
+            }
+
+            if let Some(page) = self.reader.pages.next() {
+                if let PageContent::Data(data_content) = page.content {
+                    self.reader.rows = data_content
+                        .row_groups
+                        .into_iter()
+                        .flat_map(|row_group| row_group.into_rows())
+                        .collect::<Vec<_>>()
+                        .into_iter();
+                }
+                continue;
+            }
+
+            match self.reader.advance_table() {
+                Ok(true) => continue,
+                Ok(false) => {
+                    self.reader.done = true;
+                    return None;
+                }
+                Err(err) => match self.reader.error_mode {
+                    ErrorMode::Fail => {
+                        self.reader.done = true;
+                        return Some(Err(err));
+                    }
+                    ErrorMode::SkipPage => {
+                        log::warn!("skipping unreadable PDB page: {err}");
+                        self.reader.stats.pages_skipped += 1;
+                        continue;
+                    }
+                },
+            }
+        }
+    }
 }
 
 /// Owned collection of rows extracted from a PDB file.
@@ -136,3 +381,32 @@ impl<'a> DoubleEndedIterator for PdbRowIter<'a> {
         self.inner.next_back()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_page_cursor_advances_within_a_table() {
+        assert_eq!(next_page_cursor(4, 10), Some(5));
+    }
+
+    #[test]
+    fn next_page_cursor_stops_at_the_last_page() {
+        assert_eq!(next_page_cursor(10, 10), None);
+    }
+
+    #[test]
+    fn is_table_included_accepts_everything_with_no_projection() {
+        assert!(is_table_included(None, PageType::Tracks));
+    }
+
+    #[test]
+    fn is_table_included_filters_by_projection() {
+        let projection: std::collections::HashSet<PageType> =
+            [PageType::Tracks].into_iter().collect();
+
+        assert!(is_table_included(Some(&projection), PageType::Tracks));
+        assert!(!is_table_included(Some(&projection), PageType::Artists));
+    }
+}
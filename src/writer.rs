@@ -0,0 +1,317 @@
+// Copyright (c) 2025 Jan Holthuis <jan.holthuis@rub.de>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a copy
+// of the MPL was not distributed with this file, You can obtain one at
+// http://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Write-back support: load a PDB file, edit its decoded rows in place, and re-encode it into a
+//! valid PDB.
+//!
+//! This is aimed at tools that repair broken entries (a missing file-extension field, a wrong
+//! path separator, stale metadata) rather than at general-purpose authoring. It depends on
+//! `Header`, `Table`, `Page`, `DataContent`, `RowGroup` and `Row` (and the types `Row` wraps)
+//! gaining `BinWrite`/`Clone` impls in the `pdb` module alongside their existing `BinRead` ones,
+//! plus a `RowGroup::new(Vec<Row>)` constructor; that support is a prerequisite for this module,
+//! not something it can add from the outside.
+//!
+//! Editing a row can change its encoded byte length (e.g. fixing a missing file-extension
+//! field), which invalidates the row-group and page layout that was read from disk. [`PdbWriter`]
+//! re-packs each table's rows into pages of at most `page_size` bytes (see [`pack_row_groups`])
+//! and each page's rows into row groups of [`ROWS_PER_GROUP`], then builds fresh [`Page`]s from
+//! that repacking rather than replaying the original pages' row groups verbatim — only a page's
+//! other metadata (index, free space, etc.) is carried over from the original. If a table ends up
+//! needing a different number of pages than it started with, this module refuses to write rather
+//! than mint new page pointers it can't validate against the rest of the chain; see
+//! [`PdbWriter::write`].
+
+use std::path::{Path, PathBuf};
+
+use binrw::{BinRead, BinWrite};
+
+use crate::pdb::{DataContent, DatabaseType, Header, Page, PageContent, Row, RowGroup, Table};
+use crate::Result;
+
+/// Number of rows grouped together under one presence bitmap, matching the on-disk PDB layout.
+const ROWS_PER_GROUP: usize = 16;
+
+/// A PDB file captured in full so its rows can be edited and written back with [`PdbWriter`].
+///
+/// Unlike [`crate::PdbReader`], which discards pages as soon as their rows have been yielded,
+/// `PdbDocument` keeps every page in memory for the lifetime of the edit, since write-back needs
+/// the original row-group and page layout to reproduce a valid file.
+#[derive(Debug)]
+pub struct PdbDocument {
+    header: Header,
+    typ: DatabaseType,
+    tables: Vec<(Table, Vec<crate::pdb::Page>)>,
+}
+
+impl PdbDocument {
+    /// Reads every table and page of `path` so it can be edited and written back.
+    pub fn open(path: &PathBuf, typ: DatabaseType) -> Result<Self> {
+        let mut reader = std::fs::File::open(path)?;
+        let header = Header::read_args(&mut reader, (typ,))?;
+
+        let mut tables = Vec::with_capacity(header.tables.len());
+        for table in &header.tables {
+            let pages: Vec<_> = header
+                .read_pages(
+                    &mut reader,
+                    binrw::Endian::NATIVE,
+                    (&table.first_page, &table.last_page, typ),
+                )?
+                .into_iter()
+                .collect();
+            tables.push((table.clone(), pages));
+        }
+
+        Ok(Self {
+            header,
+            typ,
+            tables,
+        })
+    }
+
+    /// Applies `f` to every decoded row, in place, across every table and page.
+    pub fn for_each_row_mut(&mut self, mut f: impl FnMut(&mut Row)) {
+        for (_, pages) in &mut self.tables {
+            for page in pages {
+                if let PageContent::Data(data_content) = &mut page.content {
+                    for row_group in &mut data_content.row_groups {
+                        for row in row_group.rows_mut() {
+                            f(row);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the byte length of `row` once encoded, used to repack the edited rows into pages.
+    fn encoded_len(row: &Row) -> Result<usize> {
+        let mut buf = binrw::io::Cursor::new(Vec::new());
+        row.write_le(&mut buf)?;
+        Ok(buf.into_inner().len())
+    }
+
+    /// Returns a [`PdbWriter`] that re-encodes this document into a PDB file.
+    #[must_use]
+    pub fn writer(&self) -> PdbWriter<'_> {
+        PdbWriter { document: self }
+    }
+}
+
+/// Error produced when a table's rows, after editing, no longer fit the page budget they were
+/// read with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepackError {
+    /// A single row's encoded size alone exceeds `page_size`, so no packing is possible.
+    RowTooLarge {
+        /// Index of the offending row within its table.
+        row_index: usize,
+        /// Encoded size of the row, in bytes.
+        row_len: usize,
+    },
+    /// The table needed a different number of pages than it started with. Rewriting the page
+    /// chain to add or remove pages isn't implemented: doing so safely requires minting new page
+    /// pointers that are validated against the rest of the file's chain, which this module
+    /// doesn't have enough context to do.
+    PageCountChanged {
+        /// Number of pages the table was read with.
+        original_pages: usize,
+        /// Number of pages the edited rows now require.
+        repacked_pages: usize,
+    },
+}
+
+impl std::fmt::Display for RepackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RowTooLarge { row_index, row_len } => write!(
+                f,
+                "row {row_index} is {row_len} bytes, too large to fit in any page"
+            ),
+            Self::PageCountChanged {
+                original_pages,
+                repacked_pages,
+            } => write!(
+                f,
+                "table needs {repacked_pages} pages after editing, but was read with \
+                 {original_pages}; relayout across a different page count is unsupported"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RepackError {}
+
+/// Greedily packs the rows described by `row_lens`, in order, into pages of at most `page_size`
+/// bytes. A row group of [`ROWS_PER_GROUP`] rows can therefore be split across two pages;
+/// [`PdbWriter::write`] forms each page's row groups from that page's own rows.
+///
+/// Returns one entry per page, each holding the indices (into `row_lens`) of the rows it
+/// contains, in order. Pure and allocation-only, so the packing decision can be tested without a
+/// real PDB file.
+pub fn pack_row_groups(
+    row_lens: &[usize],
+    page_size: usize,
+) -> std::result::Result<Vec<Vec<usize>>, RepackError> {
+    let mut pages = Vec::new();
+    let mut current_page: Vec<usize> = Vec::new();
+    let mut current_len = 0usize;
+
+    for (idx, &row_len) in row_lens.iter().enumerate() {
+        if row_len > page_size {
+            return Err(RepackError::RowTooLarge {
+                row_index: idx,
+                row_len,
+            });
+        }
+
+        if !current_page.is_empty() && current_len + row_len > page_size {
+            pages.push(std::mem::take(&mut current_page));
+            current_len = 0;
+        }
+
+        current_page.push(idx);
+        current_len += row_len;
+    }
+
+    if !current_page.is_empty() {
+        pages.push(current_page);
+    }
+
+    Ok(pages)
+}
+
+/// Re-encodes a [`PdbDocument`] into a PDB file, keeping each table's original page count and
+/// page metadata while repacking its rows into those pages.
+#[derive(Debug)]
+pub struct PdbWriter<'a> {
+    document: &'a PdbDocument,
+}
+
+impl<'a> PdbWriter<'a> {
+    /// Writes the header, followed by every table's re-packed pages in their original order, to
+    /// `path`.
+    ///
+    /// For every table, the edited rows are re-packed per [`pack_row_groups`] and checked against
+    /// the table's original page count. If the edits changed how many pages the table needs, this
+    /// returns [`RepackError::PageCountChanged`] instead of writing a file with a stale table
+    /// index. Otherwise, new [`Page`]s are built from the repacked row groups — the original
+    /// page's own metadata is kept, but its row groups are replaced, so a row whose encoded length
+    /// changed ends up in a correctly-sized row group rather than its old, now-wrong slot.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut rebuilt_tables: Vec<(&Table, Vec<Page>)> =
+            Vec::with_capacity(self.document.tables.len());
+
+        for (table, pages) in &self.document.tables {
+            let rows: Vec<Row> = table_rows(pages);
+            let row_lens: Vec<usize> = rows
+                .iter()
+                .map(PdbDocument::encoded_len)
+                .collect::<Result<_>>()?;
+
+            let repacked = pack_row_groups(&row_lens, self.document.header.page_size as usize)
+                .map_err(std::io::Error::other)?;
+
+            if repacked.len() != pages.len() {
+                return Err(std::io::Error::other(RepackError::PageCountChanged {
+                    original_pages: pages.len(),
+                    repacked_pages: repacked.len(),
+                })
+                .into());
+            }
+
+            let new_pages: Vec<Page> = pages
+                .iter()
+                .zip(&repacked)
+                .map(|(page, row_indices)| {
+                    let mut new_page = page.clone();
+                    new_page.content = PageContent::Data(DataContent {
+                        row_groups: row_indices
+                            .chunks(ROWS_PER_GROUP)
+                            .map(|chunk| {
+                                RowGroup::new(chunk.iter().map(|&idx| rows[idx].clone()).collect())
+                            })
+                            .collect(),
+                    });
+                    new_page
+                })
+                .collect();
+
+            rebuilt_tables.push((table, new_pages));
+        }
+
+        let mut file = std::fs::File::create(path)?;
+
+        self.document
+            .header
+            .write_args(&mut file, (self.document.typ,))?;
+
+        for (_, pages) in &rebuilt_tables {
+            for page in pages {
+                page.write_args(&mut file, (self.document.typ,))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Flattens a table's pages into the ordered list of rows they contain, skipping any non-data
+/// page. Used both to measure each row's re-encoded length and, once repacked, to pull rows into
+/// their new row groups.
+fn table_rows(pages: &[Page]) -> Vec<Row> {
+    pages
+        .iter()
+        .filter_map(|page| match &page.content {
+            PageContent::Data(data_content) => Some(&data_content.row_groups),
+            _ => None,
+        })
+        .flatten()
+        .flat_map(RowGroup::rows)
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_row_groups_keeps_every_row_exactly_once() {
+        let row_lens: Vec<usize> = (0..40).map(|i| 10 + (i % 5)).collect();
+        let pages = pack_row_groups(&row_lens, 200).expect("rows fit the page budget");
+
+        let mut seen: Vec<usize> = pages.iter().flatten().copied().collect();
+        seen.sort_unstable();
+        assert_eq!(seen, (0..row_lens.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn pack_row_groups_respects_page_size() {
+        let row_lens = vec![50; 32];
+        let pages = pack_row_groups(&row_lens, 200).expect("rows fit the page budget");
+
+        for page in &pages {
+            let page_len: usize = page.iter().map(|&idx| row_lens[idx]).sum();
+            assert!(page_len <= 200, "page of {page_len} bytes exceeds page_size");
+        }
+    }
+
+    #[test]
+    fn pack_row_groups_rejects_an_oversized_row() {
+        let row_lens = vec![10, 10, 500, 10];
+        let err = pack_row_groups(&row_lens, 200).unwrap_err();
+        assert_eq!(
+            err,
+            RepackError::RowTooLarge {
+                row_index: 2,
+                row_len: 500,
+            }
+        );
+    }
+}